@@ -0,0 +1,200 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use futures_util::StreamExt;
+use reqwest::Url;
+use tokio::io::AsyncWriteExt;
+use tokio::sync::{Mutex, Semaphore};
+
+/// Persistent on-disk cache for fetched image assets.
+///
+/// Entries are keyed by the MD5 of the remote URL string and stored as
+/// `<cache_dir>/<md5>.<ext>`. Concurrent requests for the same URL are
+/// coalesced behind a per-URL semaphore, so that a burst of calls for one
+/// thumbnail results in a single network fetch while the rest wait and then
+/// read the freshly written file from disk.
+pub struct AssetCache {
+    cache_dir: PathBuf,
+    locks: Mutex<HashMap<Url, Arc<Semaphore>>>,
+}
+
+impl AssetCache {
+    /// Create a cache rooted at `cache_dir`. The directory is created lazily on
+    /// the first write, so construction never touches the filesystem.
+    pub fn new(cache_dir: PathBuf) -> Self {
+        AssetCache {
+            cache_dir,
+            locks: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Return the on-disk path an asset with the given remote URL would occupy.
+    fn cache_path(&self, url: &str) -> PathBuf {
+        let digest = md5::compute(url.as_bytes());
+        self.cache_dir
+            .join(format!("{:x}.{}", digest, extension_for(url)))
+    }
+
+    /// Fetch the per-URL semaphore, creating it on first use. Holding the
+    /// single permit serializes network fetches for that exact URL.
+    async fn semaphore_for(&self, url: &Url) -> Arc<Semaphore> {
+        let mut locks = self.locks.lock().await;
+        locks
+            .entry(url.clone())
+            .or_insert_with(|| Arc::new(Semaphore::new(1)))
+            .clone()
+    }
+
+    /// Return the bytes for `url`, downloading and caching them with `client`
+    /// on a miss. Concurrent callers for the same URL coalesce onto a single
+    /// fetch.
+    pub async fn get_or_fetch(
+        &self,
+        url: &str,
+        client: &reqwest::Client,
+    ) -> Result<Vec<u8>, String> {
+        let parsed = Url::parse(url).map_err(|e| format!("Invalid URL: {}", e))?;
+        let path = self.cache_path(url);
+
+        // Fast path: serve an already-cached file without taking the per-URL lock.
+        if let Ok(bytes) = tokio::fs::read(&path).await {
+            println!("[AssetCache] HIT {}", url);
+            return Ok(bytes);
+        }
+
+        let semaphore = self.semaphore_for(&parsed).await;
+        let result = self.fetch_under_lock(url, &path, &semaphore, client).await;
+        // Drop this URL's entry once we are its last waiter, so the lock table
+        // stays bounded by in-flight URLs rather than every URL ever fetched.
+        self.release_semaphore(&parsed, semaphore).await;
+        result
+    }
+
+    /// Acquire the per-URL permit, re-check the cache in case a coalesced
+    /// sibling populated it, and otherwise download and store the asset.
+    async fn fetch_under_lock(
+        &self,
+        url: &str,
+        path: &Path,
+        semaphore: &Arc<Semaphore>,
+        client: &reqwest::Client,
+    ) -> Result<Vec<u8>, String> {
+        let _permit = semaphore
+            .acquire()
+            .await
+            .map_err(|e| format!("Failed to acquire cache lock: {}", e))?;
+
+        // Another task may have populated the cache while we waited for the permit.
+        if let Ok(bytes) = tokio::fs::read(path).await {
+            println!("[AssetCache] HIT (coalesced) {}", url);
+            return Ok(bytes);
+        }
+
+        println!("[AssetCache] MISS {}", url);
+        let response = client
+            .get(url)
+            .send()
+            .await
+            .map_err(|e| format!("Request failed: {}", e))?;
+
+        if !response.status().is_success() {
+            return Err(format!("Failed to fetch image: {}", response.status()));
+        }
+
+        let written = self.stream_to_cache(path, response).await?;
+        println!("[AssetCache] STORED {} ({} bytes)", url, written);
+
+        // The download streams to disk a chunk at a time, but the commands
+        // return owned bytes, so the stored file is read back whole here —
+        // peak memory on a miss is one full asset, as it is on a hit. The
+        // cache file's path is not exposed outside this module.
+        tokio::fs::read(path)
+            .await
+            .map_err(|e| format!("Failed to read cache file: {}", e))
+    }
+
+    /// Remove a URL's coalescing entry once no other waiter is holding it. Two
+    /// references remain when we are the last caller: the map's and our own
+    /// clone, so a strong count of 2 means the entry is safe to drop.
+    async fn release_semaphore(&self, url: &Url, semaphore: Arc<Semaphore>) {
+        let mut locks = self.locks.lock().await;
+        if Arc::strong_count(&semaphore) == 2 {
+            locks.remove(url);
+        }
+    }
+
+    /// Stream a response body chunk-by-chunk into the cache, staging to a
+    /// sibling temp file and renaming it into place atomically. Writing
+    /// incrementally avoids holding a multi-megabyte image in memory while it
+    /// downloads, and a crashed fetch never leaves a truncated entry behind.
+    /// Returns the number of bytes written.
+    async fn stream_to_cache(
+        &self,
+        path: &Path,
+        response: reqwest::Response,
+    ) -> Result<usize, String> {
+        tokio::fs::create_dir_all(&self.cache_dir)
+            .await
+            .map_err(|e| format!("Failed to create cache dir: {}", e))?;
+
+        let tmp = path.with_extension("tmp");
+        let mut file = tokio::fs::File::create(&tmp)
+            .await
+            .map_err(|e| format!("Failed to write cache file: {}", e))?;
+
+        let mut written = 0usize;
+        let mut stream = response.bytes_stream();
+        while let Some(chunk) = stream.next().await {
+            let chunk = chunk.map_err(|e| format!("Failed to read chunk: {}", e))?;
+            file.write_all(&chunk)
+                .await
+                .map_err(|e| format!("Failed to write cache file: {}", e))?;
+            written += chunk.len();
+        }
+        file.flush()
+            .await
+            .map_err(|e| format!("Failed to flush cache file: {}", e))?;
+        drop(file);
+
+        tokio::fs::rename(&tmp, path)
+            .await
+            .map_err(|e| format!("Failed to commit cache file: {}", e))?;
+        Ok(written)
+    }
+
+    /// Remove every cached asset, leaving the cache directory itself in place.
+    pub async fn clear(&self) -> Result<(), String> {
+        let mut entries = match tokio::fs::read_dir(&self.cache_dir).await {
+            Ok(entries) => entries,
+            // Nothing has been cached yet; treat an absent directory as cleared.
+            Err(_) => return Ok(()),
+        };
+
+        while let Some(entry) = entries
+            .next_entry()
+            .await
+            .map_err(|e| format!("Failed to read cache dir: {}", e))?
+        {
+            tokio::fs::remove_file(entry.path())
+                .await
+                .map_err(|e| format!("Failed to remove cache file: {}", e))?;
+        }
+
+        println!("[AssetCache] cleared {}", self.cache_dir.display());
+        Ok(())
+    }
+}
+
+/// Best-effort file extension for a remote URL, derived from its path. Falls
+/// back to `jpg`, which is what the scraper sees for the overwhelming majority
+/// of Takealot product imagery.
+fn extension_for(url: &str) -> String {
+    let path = url.split(['?', '#']).next().unwrap_or(url);
+    path.rsplit('/')
+        .next()
+        .and_then(|name| name.rsplit_once('.'))
+        .map(|(_, ext)| ext.to_ascii_lowercase())
+        .filter(|ext| !ext.is_empty() && ext.len() <= 5 && ext.chars().all(|c| c.is_ascii_alphanumeric()))
+        .unwrap_or_else(|| "jpg".to_string())
+}
@@ -1,9 +1,96 @@
+mod asset_cache;
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
 use base64::{engine::general_purpose::STANDARD, Engine};
+use tauri::Manager;
+use tokio::sync::Mutex;
 
-#[tauri::command]
-async fn fetch_image(url: String, proxy_url: Option<String>) -> Result<String, String> {
-    println!("[fetch_image] Called with URL: {}", url);
+use asset_cache::AssetCache;
+
+/// Shared backing store handed to both the Tauri commands and the custom
+/// `scraped://` protocol handler. Holding the cache and the client pool in one
+/// managed handle guarantees every image-fetching path writes to and reads from
+/// the same on-disk cache and reuses the same keep-alive connections.
+pub struct StateManager {
+    pub cache: Arc<AssetCache>,
+    /// Clients built once and reused, keyed by proxy string (`None` for a
+    /// direct connection). Cloning a [`reqwest::Client`] shares its connection
+    /// pool and TLS session cache, so repeated fetches to the same host reuse
+    /// live connections instead of rebuilding a client per call.
+    clients: Mutex<HashMap<Option<String>, reqwest::Client>>,
+    /// The proxy the frontend is currently routing through, updated by each
+    /// command. The `scraped://` handler's URL carries no proxy of its own, so
+    /// it reads this to fetch over the same anti-bot route as the commands.
+    active_proxy: Mutex<Option<String>>,
+}
+
+impl StateManager {
+    /// Create an empty state manager; clients are built lazily on first use for
+    /// each distinct proxy.
+    pub fn new(cache: Arc<AssetCache>) -> Self {
+        StateManager {
+            cache,
+            clients: Mutex::new(HashMap::new()),
+            active_proxy: Mutex::new(None),
+        }
+    }
+
+    /// Record the proxy the frontend is currently routing through so the
+    /// `scraped://` protocol handler can reuse it. An empty string is
+    /// normalised to `None` (a direct connection).
+    pub async fn set_active_proxy(&self, proxy_url: &Option<String>) {
+        let normalized = proxy_url
+            .as_ref()
+            .filter(|s| !s.is_empty())
+            .map(|s| s.to_string());
+        *self.active_proxy.lock().await = normalized;
+    }
+
+    /// The proxy most recently set by a command, for paths that carry no proxy
+    /// of their own.
+    pub async fn active_proxy(&self) -> Option<String> {
+        self.active_proxy.lock().await.clone()
+    }
+
+    /// Return a pooled client for the given proxy, building and caching it on
+    /// first use. An empty proxy string is treated as a direct connection.
+    pub async fn client(&self, proxy_url: &Option<String>) -> Result<reqwest::Client, String> {
+        let key = proxy_url
+            .as_ref()
+            .filter(|s| !s.is_empty())
+            .map(|s| s.to_string());
+
+        let mut clients = self.clients.lock().await;
+        if let Some(client) = clients.get(&key) {
+            return Ok(client.clone());
+        }
+
+        let client = build_client(&key)?;
+        clients.insert(key, client.clone());
+        Ok(client)
+    }
+}
+
+/// Sniff a content type from the leading magic bytes of a decoded asset,
+/// falling back to `application/octet-stream` for anything unrecognised. The
+/// cache stores raw bytes without response headers, so the scheme handler
+/// recovers the MIME type from the payload itself rather than trusting the URL.
+fn guess_mime(bytes: &[u8]) -> &'static str {
+    match bytes {
+        [0xFF, 0xD8, 0xFF, ..] => "image/jpeg",
+        [0x89, b'P', b'N', b'G', ..] => "image/png",
+        [b'G', b'I', b'F', b'8', ..] => "image/gif",
+        [b'R', b'I', b'F', b'F', _, _, _, _, b'W', b'E', b'B', b'P', ..] => "image/webp",
+        [0x42, 0x4D, ..] => "image/bmp",
+        _ => "application/octet-stream",
+    }
+}
 
+/// Build a reqwest client configured the way the scraper needs: lenient TLS for
+/// Takealot's CDN, a short connection pool, and an optional all-protocols proxy.
+fn build_client(proxy_url: &Option<String>) -> Result<reqwest::Client, String> {
     let mut client_builder = reqwest::Client::builder()
         .user_agent("Mozilla/5.0 (Macintosh; Intel Mac OS X 10_15_7) AppleWebKit/537.36")
         .danger_accept_invalid_certs(true)
@@ -13,106 +100,312 @@ async fn fetch_image(url: String, proxy_url: Option<String>) -> Result<String, S
 
     if let Some(proxy_str) = proxy_url {
         if !proxy_str.is_empty() {
-            println!("[fetch_image] Using proxy: {}", proxy_str);
-            let proxy = reqwest::Proxy::all(&proxy_str)
-                .map_err(|e| {
-                    let err_msg = format!("Failed to create proxy: {}", e);
-                    println!("[fetch_image] ERROR: {}", err_msg);
-                    err_msg
-                })?;
+            println!("Using proxy: {}", proxy_str);
+            let proxy = reqwest::Proxy::all(proxy_str)
+                .map_err(|e| format!("Failed to create proxy: {}", e))?;
             client_builder = client_builder.proxy(proxy);
         } else {
-            println!("[fetch_image] No proxy configured");
+            println!("No proxy configured");
         }
     } else {
-        println!("[fetch_image] No proxy configured");
+        println!("No proxy configured");
     }
 
-    let client = client_builder.build().map_err(|e| {
+    client_builder.build().map_err(|e| {
         let err_msg = format!("Failed to build client: {}", e);
-        println!("[fetch_image] ERROR: {}", err_msg);
+        println!("ERROR: {}", err_msg);
         err_msg
-    })?;
+    })
+}
 
-    println!("[fetch_image] Sending request...");
-    let response = client
-        .get(&url)
-        .send()
-        .await
-        .map_err(|e| {
-            let err_msg = format!("Request failed: {}", e);
-            println!("[fetch_image] ERROR: {}", err_msg);
-            err_msg
-        })?;
-
-    println!("[fetch_image] Response status: {}", response.status());
-
-    if !response.status().is_success() {
-        let err_msg = format!("Failed to fetch image: {}", response.status());
-        println!("[fetch_image] ERROR: {}", err_msg);
-        return Err(err_msg);
+/// Resolve an asset URL of any supported scheme to its raw bytes. `data:` URLs
+/// are decoded in-process, `file:` URLs are read from disk, and everything else
+/// falls through to the cached HTTP fetch — so callers never have to special
+/// case inline assets embedded in scraped markup.
+async fn resolve_bytes(
+    url: &str,
+    state: &StateManager,
+    proxy_url: &Option<String>,
+) -> Result<Vec<u8>, String> {
+    if let Some(rest) = url.strip_prefix("data:") {
+        println!("[resolve] inline data URL ({} chars)", rest.len());
+        return decode_data_url(rest);
+    }
+    if url.starts_with("file:") {
+        return read_file_url(url).await;
     }
 
-    let bytes = response.bytes().await.map_err(|e| {
-        let err_msg = format!("Failed to read bytes: {}", e);
-        println!("[fetch_image] ERROR: {}", err_msg);
-        err_msg
-    })?;
+    state.set_active_proxy(proxy_url).await;
+    let client = state.client(proxy_url).await?;
+    state.cache.get_or_fetch(url, &client).await
+}
+
+/// Decode the payload of a `data:` URL (the part after `data:`), handling both
+/// base64-encoded and percent-encoded bodies.
+fn decode_data_url(rest: &str) -> Result<Vec<u8>, String> {
+    let (meta, data) = rest
+        .split_once(',')
+        .ok_or_else(|| "Malformed data URL".to_string())?;
+
+    if meta.split(';').any(|token| token.eq_ignore_ascii_case("base64")) {
+        STANDARD
+            .decode(data)
+            .map_err(|e| format!("Failed to decode data URL: {}", e))
+    } else {
+        Ok(urlencoding::decode_binary(data.as_bytes()).into_owned())
+    }
+}
+
+/// The media type declared in a `data:` URL's metadata (the part after `data:`
+/// and before the comma), defaulting to `text/plain` per the data-URL spec when
+/// none is given. Preserving the declared type matters for formats magic-byte
+/// sniffing can't recognise, such as `image/svg+xml`.
+fn data_url_media_type(rest: &str) -> String {
+    let meta = rest.split_once(',').map(|(meta, _)| meta).unwrap_or(rest);
+    meta.split(';')
+        .next()
+        .filter(|s| !s.is_empty())
+        .unwrap_or("text/plain")
+        .to_string()
+}
+
+/// Read the bytes of a `file:` URL from local disk. The path is percent-decoded
+/// first, so an escaped URL like `file:///my%20image.png` resolves to the real
+/// `/my image.png` on disk.
+async fn read_file_url(url: &str) -> Result<Vec<u8>, String> {
+    let raw = url
+        .strip_prefix("file://")
+        .or_else(|| url.strip_prefix("file:"))
+        .unwrap_or(url);
+    let path = urlencoding::decode(raw)
+        .map_err(|e| format!("Failed to decode file URL: {}", e))?
+        .into_owned();
+    println!("[resolve] local file {}", path);
+    tokio::fs::read(&path)
+        .await
+        .map_err(|e| format!("Failed to read file {}: {}", path, e))
+}
+
+#[tauri::command]
+async fn fetch_image(
+    url: String,
+    proxy_url: Option<String>,
+    state: tauri::State<'_, StateManager>,
+) -> Result<String, String> {
+    println!("[fetch_image] Called with URL: {}", url);
+
+    let bytes = resolve_bytes(&url, &state, &proxy_url).await?;
+    // Trust a `data:` URL's declared media type rather than re-sniffing it;
+    // magic bytes can't recover text-based formats like SVG.
+    let mime = match url.strip_prefix("data:") {
+        Some(rest) => data_url_media_type(rest),
+        None => guess_mime(&bytes).to_string(),
+    };
 
     println!("[fetch_image] Received {} bytes", bytes.len());
     let base64_data = STANDARD.encode(&bytes);
     println!("[fetch_image] SUCCESS: Encoded to base64 ({} chars)", base64_data.len());
 
-    Ok(format!("data:image/jpeg;base64,{}", base64_data))
+    Ok(format!("data:{};base64,{}", mime, base64_data))
 }
 
 #[tauri::command]
-async fn fetch_image_buffer(url: String, proxy_url: Option<String>) -> Result<Vec<u8>, String> {
+async fn fetch_image_buffer(
+    url: String,
+    proxy_url: Option<String>,
+    state: tauri::State<'_, StateManager>,
+) -> Result<Vec<u8>, String> {
     println!("fetch_image_buffer called with url: {}", url);
 
-    let mut client_builder = reqwest::Client::builder()
-        .user_agent("Mozilla/5.0 (Macintosh; Intel Mac OS X 10_15_7) AppleWebKit/537.36")
-        .danger_accept_invalid_certs(true)
-        .pool_max_idle_per_host(5)
-        .pool_idle_timeout(std::time::Duration::from_secs(30))
-        .timeout(std::time::Duration::from_secs(30));
+    let bytes = resolve_bytes(&url, &state, &proxy_url).await?;
 
-    if let Some(proxy_str) = proxy_url {
-        if !proxy_str.is_empty() {
-            println!("Using proxy: {}", proxy_str);
-            let proxy = reqwest::Proxy::all(&proxy_str)
-                .map_err(|e| format!("Failed to create proxy: {}", e))?;
-            client_builder = client_builder.proxy(proxy);
-        }
+    println!("Got {} bytes", bytes.len());
+    Ok(bytes)
+}
+
+/// A (possibly partial) asset response returned to the frontend, carrying the
+/// HTTP status and the `Content-Range`/`Content-Length` metadata needed to
+/// progressively load or resume a large download.
+#[derive(serde::Serialize)]
+struct RangeResponse {
+    status: u16,
+    body: Vec<u8>,
+    content_range: Option<String>,
+    content_length: Option<u64>,
+}
+
+/// Format a `Range` header value from optional byte offsets: `start-end`,
+/// `start-` (open-ended) or `-end` (the final `end` bytes).
+fn format_range(start: Option<u64>, end: Option<u64>) -> String {
+    match (start, end) {
+        (Some(s), Some(e)) => format!("bytes={}-{}", s, e),
+        (Some(s), None) => format!("bytes={}-", s),
+        (None, Some(e)) => format!("bytes=-{}", e),
+        (None, None) => "bytes=0-".to_string(),
     }
+}
+
+#[tauri::command]
+async fn fetch_image_range(
+    url: String,
+    proxy_url: Option<String>,
+    start: Option<u64>,
+    end: Option<u64>,
+    state: tauri::State<'_, StateManager>,
+) -> Result<RangeResponse, String> {
+    println!("[fetch_image_range] Called with URL: {} ({:?}-{:?})", url, start, end);
 
-    let client = client_builder.build().map_err(|e| {
-        println!("Failed to build client: {}", e);
-        e.to_string()
-    })?;
+    state.set_active_proxy(&proxy_url).await;
+    let client = state.client(&proxy_url).await?;
 
+    // No range requested: preserve the full-fetch behavior and the cache path.
+    if start.is_none() && end.is_none() {
+        let bytes = state.cache.get_or_fetch(&url, &client).await?;
+        let len = bytes.len() as u64;
+        return Ok(RangeResponse {
+            status: 200,
+            content_length: Some(len),
+            content_range: None,
+            body: bytes,
+        });
+    }
+
+    let range = format_range(start, end);
+    println!("[fetch_image_range] Requesting {}", range);
     let response = client
         .get(&url)
+        .header(reqwest::header::RANGE, &range)
         .send()
         .await
-        .map_err(|e| {
-            println!("Failed to send request: {}", e);
-            e.to_string()
-        })?;
+        .map_err(|e| format!("Request failed: {}", e))?;
+
+    let status = response.status();
+    if !status.is_success() {
+        return Err(format!("Failed to fetch image: {}", status));
+    }
+
+    let header = |name: reqwest::header::HeaderName| {
+        response
+            .headers()
+            .get(&name)
+            .and_then(|v| v.to_str().ok())
+            .map(|s| s.to_string())
+    };
+    let content_range = header(reqwest::header::CONTENT_RANGE);
+    let content_length = header(reqwest::header::CONTENT_LENGTH).and_then(|v| v.parse().ok());
+
+    // This command returns the partial body whole in `RangeResponse`, so the
+    // requested range is buffered in memory rather than streamed. The
+    // `scraped://` protocol streams cache misses chunk-by-chunk to disk, but it
+    // too reads the stored asset back whole before it responds.
+    let body = response
+        .bytes()
+        .await
+        .map_err(|e| format!("Failed to read response body: {}", e))?
+        .to_vec();
+
+    println!("[fetch_image_range] Received {} bytes (status {})", body.len(), status);
+    Ok(RangeResponse {
+        status: status.as_u16(),
+        body,
+        content_range,
+        content_length,
+    })
+}
+
+#[tauri::command]
+async fn clear_image_cache(state: tauri::State<'_, StateManager>) -> Result<(), String> {
+    println!("[clear_image_cache] Called");
+    state.cache.clear().await
+}
+
+/// Decode a `scraped://<percent-encoded-remote-url>` scheme URL back into the
+/// original remote URL the frontend asked for.
+fn decode_scheme_url(uri: &str) -> Result<String, String> {
+    let encoded = uri
+        .strip_prefix("scraped://")
+        .or_else(|| uri.strip_prefix("scraped:"))
+        .ok_or_else(|| format!("Unexpected scheme URL: {}", uri))?
+        .trim_start_matches('/');
+    urlencoding::decode(encoded)
+        .map(|decoded| decoded.into_owned())
+        .map_err(|e| format!("Failed to decode scheme URL: {}", e))
+}
 
-    println!("Response status: {}", response.status());
+/// Resolve a `scraped://` request to raw image bytes via the shared cache and
+/// wrap them in an HTTP response with a sniffed content type, so the webview
+/// can load product imagery with a plain `img.src` instead of a base64 payload
+/// shuttled across the IPC bridge.
+async fn serve_scraped(
+    app: &tauri::AppHandle,
+    uri: &str,
+    range: Option<String>,
+) -> Result<tauri::http::Response<Vec<u8>>, String> {
+    let remote = decode_scheme_url(uri)?;
+    println!("[scraped] Resolving {}", remote);
 
-    if !response.status().is_success() {
-        return Err(format!("Failed to fetch image: {}", response.status()));
+    let state = app.state::<StateManager>();
+    let proxy = state.active_proxy().await;
+    let client = state.client(&proxy).await?;
+    let bytes = state.cache.get_or_fetch(&remote, &client).await?;
+    let mime = guess_mime(&bytes);
+
+    // Honor a single-range request by slicing the cached bytes and replying
+    // with a 206 partial response; otherwise return the whole asset.
+    if let Some((start, end)) = range.as_deref().and_then(|h| parse_range(h, bytes.len() as u64)) {
+        let total = bytes.len() as u64;
+        let slice = bytes[start as usize..=end as usize].to_vec();
+        println!("[scraped] Serving bytes {}-{}/{}", start, end, total);
+        return tauri::http::Response::builder()
+            .status(206)
+            .header("Content-Type", mime)
+            .header("Content-Range", format!("bytes {}-{}/{}", start, end, total))
+            .header("Content-Length", (end - start + 1).to_string())
+            .header("Accept-Ranges", "bytes")
+            .header("Access-Control-Allow-Origin", "*")
+            .body(slice)
+            .map_err(|e| format!("Failed to build response: {}", e));
     }
 
-    let bytes = response.bytes().await.map_err(|e| {
-        println!("Failed to get bytes: {}", e);
-        e.to_string()
-    })?;
+    tauri::http::Response::builder()
+        .status(200)
+        .header("Content-Type", mime)
+        .header("Accept-Ranges", "bytes")
+        .header("Access-Control-Allow-Origin", "*")
+        .body(bytes)
+        .map_err(|e| format!("Failed to build response: {}", e))
+}
 
-    println!("Got {} bytes", bytes.len());
-    Ok(bytes.to_vec())
+/// Parse a single-range `Range: bytes=start-end` header against a known total
+/// length, returning an inclusive, clamped `(start, end)` pair. Suffix ranges
+/// (`bytes=-N`) and open-ended ranges (`bytes=N-`) are supported; anything
+/// unsatisfiable or malformed yields `None` so the caller falls back to a full
+/// response.
+fn parse_range(header: &str, total: u64) -> Option<(u64, u64)> {
+    if total == 0 {
+        return None;
+    }
+    let spec = header.trim().strip_prefix("bytes=")?;
+    let (start_str, end_str) = spec.split_once('-')?;
+
+    let (start, end) = match (start_str.trim(), end_str.trim()) {
+        ("", "") => return None,
+        ("", suffix) => {
+            // Last `suffix` bytes.
+            let n: u64 = suffix.parse().ok()?;
+            if n == 0 {
+                return None;
+            }
+            (total.saturating_sub(n), total - 1)
+        }
+        (start, "") => (start.parse().ok()?, total - 1),
+        (start, end) => (start.parse().ok()?, end.parse::<u64>().ok()?.min(total - 1)),
+    };
+
+    if start > end || start >= total {
+        return None;
+    }
+    Some((start, end))
 }
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
@@ -122,7 +415,40 @@ pub fn run() {
         .plugin(tauri_plugin_fs::init())
         .plugin(tauri_plugin_dialog::init())
         .plugin(tauri_plugin_http::init())
-        .invoke_handler(tauri::generate_handler![fetch_image, fetch_image_buffer])
+        .register_asynchronous_uri_scheme_protocol("scraped", |ctx, request, responder| {
+            let app = ctx.app_handle().clone();
+            let uri = request.uri().to_string();
+            let range = request
+                .headers()
+                .get(tauri::http::header::RANGE)
+                .and_then(|v| v.to_str().ok())
+                .map(|s| s.to_string());
+            tauri::async_runtime::spawn(async move {
+                let response = serve_scraped(&app, &uri, range).await.unwrap_or_else(|err| {
+                    println!("[scraped] ERROR: {}", err);
+                    tauri::http::Response::builder()
+                        .status(502)
+                        .body(err.into_bytes())
+                        .expect("failed to build error response")
+                });
+                responder.respond(response);
+            });
+        })
+        .setup(|app| {
+            let cache_dir = app
+                .path()
+                .app_cache_dir()
+                .expect("failed to resolve app cache dir")
+                .join("images");
+            app.manage(StateManager::new(Arc::new(AssetCache::new(cache_dir))));
+            Ok(())
+        })
+        .invoke_handler(tauri::generate_handler![
+            fetch_image,
+            fetch_image_buffer,
+            fetch_image_range,
+            clear_image_cache
+        ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
 }